@@ -22,7 +22,7 @@
 //!     let add_p = perf!("add fn");
 //!     let result = add(4, 4);
 //!     add_p.end();
-//!     // ^-- prints: 0.100140446 (add fn) @ [src/main.rs:9]
+//!     // ^-- prints: 100.1 ms (add fn - end) @ [src/main.rs:9]
 //!
 //!     assert_eq!(result, 8);
 //! }
@@ -60,33 +60,376 @@
 //!
 //! # Panics
 //!
-//! Panics if writing to `io::stderr` fails.
+//! Panics if writing to the configured sink fails: `io::stderr` by default, or whatever
+//! writer was given via `to_writer`.
 //!
 //!
 //! [stderr]: https://en.wikipedia.org/wiki/Standard_streams#Standard_error_(stderr)
 //!
+
+/// Controls how a [`Perf`] renders the durations it prints.
+///
+/// The default is [`Precision::Auto`], which picks whichever unit keeps the
+/// number readable. Reach for [`Precision::RawNanos`] if you want the exact
+/// `seconds.nanoseconds` form this crate used to always print, e.g. to keep
+/// older log-scraping scripts working.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    /// Pick ns/µs/ms/s (and minutes above 60s) automatically: whole nanoseconds, one decimal
+    /// for µs, ms and the seconds part of minutes, two decimals for seconds.
+    Auto,
+    /// Always print the raw `seconds.nanoseconds` form.
+    RawNanos,
+}
+
+/// Formats `elapsed` the way `precision` says to.
+fn format_elapsed(elapsed: std::time::Duration, precision: Precision) -> String {
+    match precision {
+        Precision::RawNanos => format!("{}.{:09}", elapsed.as_secs(), elapsed.subsec_nanos()),
+        Precision::Auto => format_auto(elapsed),
+    }
+}
+
+/// Rounds `x` to `decimals` decimal places, the same rounding `{:.N}` formatting would apply.
+fn round_to(x: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (x * factor).round() / factor
+}
+
+/// Picks a human-readable unit for `elapsed`, auto-scaling from nanoseconds up to minutes.
+///
+/// Units are picked against the *rounded* value, not the raw one, so a duration that rounds
+/// up to the next unit's threshold (e.g. 999.96µs rounding to "1000.0 µs") bumps into that
+/// next unit instead of overflowing the display.
+fn format_auto(elapsed: std::time::Duration) -> String {
+    let nanos = elapsed.as_nanos();
+    if nanos < 1_000 {
+        return format!("{} ns", nanos);
+    }
+
+    let micros = nanos as f64 / 1_000.0;
+    if round_to(micros, 1) < 1_000.0 {
+        return format!("{:.1} µs", micros);
+    }
+
+    let millis = nanos as f64 / 1_000_000.0;
+    if round_to(millis, 1) < 1_000.0 {
+        return format!("{:.1} ms", millis);
+    }
+
+    let secs = elapsed.as_secs_f64();
+    if round_to(secs, 2) < 60.0 {
+        return format!("{:.2} s", secs);
+    }
+
+    let mins = (secs / 60.0).floor();
+    let rem = secs - mins * 60.0;
+    if round_to(rem, 1) < 60.0 {
+        format!("{} m {:.1} s", mins as u64, rem)
+    } else {
+        format!("{} m {:.1} s", mins as u64 + 1, 0.0)
+    }
+}
+
+/// Which measurement a printed record came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MeasurementKind {
+    Lap,
+    Split,
+    End,
+    Scoped,
+    Bench,
+    Agg,
+}
+
+impl MeasurementKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            MeasurementKind::Lap => "lap",
+            MeasurementKind::Split => "split",
+            MeasurementKind::End => "end",
+            MeasurementKind::Scoped => "scoped",
+            MeasurementKind::Bench => "bench",
+            MeasurementKind::Agg => "agg",
+        }
+    }
+}
+
+/// The shape each printed measurement takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable, colored when not on non-debug Windows. This crate's original look.
+    Pretty,
+    /// One JSON object per line: `elapsed_ns`, `ident`, `label`, `kind`, `location`.
+    Json,
+    /// One tab-separated line per measurement, same fields and order as [`Format::Json`].
+    Tsv,
+}
+
+/// Where measurements are written, and in which [`Format`]. You won't build one of these
+/// directly; use [`Perf::to_writer`] and [`Perf::with_format`].
+struct Output {
+    writer: Box<dyn std::io::Write>,
+    format: Format,
+}
+
+/// Everything needed to render one printed measurement, grouped so [`Output::write_measurement`]
+/// doesn't need a long parameter list.
+struct Measurement<'a> {
+    kind: MeasurementKind,
+    ident: &'a str,
+    label: &'a str,
+    elapsed: std::time::Duration,
+    precision: Precision,
+    lap_n: Option<usize>,
+    location: Option<&'a str>,
+}
+
+impl Output {
+    fn stderr() -> Self {
+        Output {
+            writer: Box::new(std::io::stderr()),
+            format: Format::Pretty,
+        }
+    }
+
+    fn write_measurement(&mut self, m: Measurement) {
+        use std::io::Write;
+
+        let line = match self.format {
+            Format::Pretty => {
+                let rendered = format_elapsed(m.elapsed, m.precision);
+                let body = match m.kind {
+                    MeasurementKind::Lap => format!(
+                        "{} ({} - {} - lap {})",
+                        rendered,
+                        m.ident,
+                        m.label,
+                        m.lap_n.unwrap_or(1)
+                    ),
+                    MeasurementKind::Split => format!("{} ({} - {})", rendered, m.ident, m.label),
+                    MeasurementKind::End => format!("{} ({} - end)", rendered, m.ident),
+                    MeasurementKind::Scoped => format!("{} ({} - scoped end)", rendered, m.ident),
+                    MeasurementKind::Bench => format!("{} - bench: {}", m.ident, m.label),
+                    MeasurementKind::Agg => format!("{} - {}", m.ident, m.label),
+                };
+                let body = match m.location {
+                    Some(loc) => format!("{} @ {}", body, loc),
+                    None => body,
+                };
+                if cfg!(all(target_os = "windows", not(debug_assertions))) {
+                    body
+                } else {
+                    format!("\x1B[33m\x1B[1m{}\x1B[0m", body)
+                }
+            }
+            Format::Json => format!(
+                "{{\"elapsed_ns\":{},\"ident\":{},\"label\":{},\"kind\":{},\"location\":{}}}",
+                m.elapsed.as_nanos(),
+                json_string(m.ident),
+                json_string(m.label),
+                json_string(m.kind.as_str()),
+                match m.location {
+                    Some(loc) => json_string(loc),
+                    None => "null".to_string(),
+                },
+            ),
+            Format::Tsv => format!(
+                "{}\t{}\t{}\t{}\t{}",
+                m.elapsed.as_nanos(),
+                tsv_escape(m.ident),
+                tsv_escape(m.label),
+                m.kind.as_str(),
+                m.location.map(tsv_escape).unwrap_or_default(),
+            ),
+        };
+
+        writeln!(self.writer, "{}", line).expect("print-perf: failed to write measurement");
+    }
+}
+
+/// Renders `s` as a quoted, minimally-escaped JSON string.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Escapes characters that would otherwise corrupt a TSV line's field boundaries
+/// (embedded tabs and newlines) or collide with the escape sequence itself.
+fn tsv_escape(s: &str) -> String {
+    if !s.contains(['\\', '\t', '\n', '\r']) {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A source of monotonic time. Lets `Perf` be generic over where "now" comes from,
+/// so it can be unit-tested deterministically (see [`MockClock`]) or run on platforms
+/// without `std::time::Instant`.
+pub trait Clock {
+    /// An opaque point in time produced by this clock.
+    type Instant: Copy;
+
+    /// Returns the current instant.
+    fn now(&self) -> Self::Instant;
+
+    /// Returns the time elapsed since `earlier`.
+    fn elapsed(&self, earlier: Self::Instant) -> std::time::Duration;
+}
+
+/// The default [`Clock`], backed by [`std::time::Instant`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdClock;
+
+impl Clock for StdClock {
+    type Instant = std::time::Instant;
+
+    fn now(&self) -> Self::Instant {
+        std::time::Instant::now()
+    }
+
+    fn elapsed(&self, earlier: Self::Instant) -> std::time::Duration {
+        earlier.elapsed()
+    }
+}
+
+/// A [`Clock`] for deterministic tests: time never moves on its own, only when you
+/// call [`MockClock::advance`]. Cloning a `MockClock` shares the same underlying time,
+/// so you can keep a handle around after handing a clone to `Perf`.
+#[derive(Clone, Debug, Default)]
+pub struct MockClock {
+    elapsed: std::rc::Rc<std::cell::Cell<std::time::Duration>>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        MockClock::default()
+    }
+
+    /// Advances the clock by `duration`. Every `Perf` built on this clock (or a clone of it)
+    /// observes the change.
+    pub fn advance(&self, duration: std::time::Duration) {
+        self.elapsed.set(self.elapsed.get() + duration);
+    }
+}
+
+impl Clock for MockClock {
+    type Instant = std::time::Duration;
+
+    fn now(&self) -> Self::Instant {
+        self.elapsed.get()
+    }
+
+    fn elapsed(&self, earlier: Self::Instant) -> std::time::Duration {
+        self.elapsed.get() - earlier
+    }
+}
+
 /// This is what you get returned from the macro. You probably won't create this directly.
-pub struct Perf {
-    start: std::time::Instant,
+pub struct Perf<C: Clock = StdClock> {
+    clock: C,
+    start: C::Instant,
     start_line: String,
     ident: String,
-    lap: Option<std::time::Instant>,
+    lap: Option<C::Instant>,
     lap_n: Option<usize>,
+    precision: Precision,
+    output: std::cell::RefCell<Output>,
 }
 
-impl Perf {
+impl Perf<StdClock> {
     pub fn new(ident: String, start_line: String) -> Self {
+        Perf::new_with_clock(ident, start_line, StdClock)
+    }
+
+    /// Starts an aggregating timer: repeated `lap(msg)` calls bucket their duration by
+    /// label instead of printing immediately, and `AggPerf::end()` prints a
+    /// count/total/min/max/mean/p50/p95 summary per label. Useful inside loops, where a timestamp per iteration is just noise.
+    pub fn aggregate(ident: String) -> AggPerf<StdClock> {
+        AggPerf::new_with_clock(ident, StdClock)
+    }
+
+    /// Starts a scoped timer that prints its elapsed time when it is dropped, so the
+    /// measurement is taken regardless of early returns or `?` propagation. Prefer the
+    /// [`perf_scoped!`] macro so the printed location matches your call site.
+    pub fn scoped(ident: String, start_line: String) -> PerfGuard<StdClock> {
+        PerfGuard::new(ident, start_line)
+    }
+
+    /// Times `f`, prints the elapsed time the same way [`Perf::end`] would, and returns `f`'s
+    /// value. Prefer the [`perf_span!`] macro so the printed location matches your call site.
+    pub fn span<T>(label: &str, start_line: String, f: impl FnOnce() -> T) -> T {
+        let p = Perf::new(label.to_string(), start_line);
+        let result = f();
+        p.end();
+        result
+    }
+
+    /// Starts a one-shot statistical benchmark; call `.run(n, f)` on the result to execute it.
+    /// See [`Bench`].
+    pub fn bench(ident: String) -> Bench<StdClock> {
+        Bench::new(ident)
+    }
+}
+
+impl<C: Clock> Perf<C> {
+    /// Like [`Perf::new`], but measures time with a custom [`Clock`] instead of `std::time::Instant`.
+    pub fn new_with_clock(ident: String, start_line: String, clock: C) -> Self {
+        let start = clock.now();
         Perf {
-            start: std::time::Instant::now(),
+            clock,
+            start,
             start_line,
             ident,
             lap: None,
             lap_n: None,
+            precision: Precision::Auto,
+            output: std::cell::RefCell::new(Output::stderr()),
         }
     }
 
-    /// Gives the time elapsed from the last lap (or from the starting point of there has been no previous laps). 
-    /// You can have as many laps as you want. The message is printed with the measurement to identify where the 
+    /// Overrides how durations are rendered, e.g. `perf!("add fn").with_precision(Precision::RawNanos)`.
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Writes measurements to `writer` instead of stderr, e.g. to capture them into a log file.
+    pub fn to_writer<W: std::io::Write + 'static>(mut self, writer: W) -> Self {
+        self.output.get_mut().writer = Box::new(writer);
+        self
+    }
+
+    /// Renders measurements as `format` instead of the default human-readable text, e.g.
+    /// `perf!("add fn").with_format(Format::Json)` to emit one JSON object per measurement.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.output.get_mut().format = format;
+        self
+    }
+
+    /// Gives the time elapsed from the last lap (or from the starting point of there has been no previous laps).
+    /// You can have as many laps as you want. The message is printed with the measurement to identify where the
     /// measurement was done.
     pub fn lap(&mut self, msg: &str) {
         let base = match self.lap {
@@ -96,77 +439,320 @@ impl Perf {
 
         let lap_n = self.lap_n.unwrap_or(1);
 
-        let elapsed = base.elapsed();
-        if cfg!(all(target_os = "windows", not(debug_assertions))) {
-            eprintln!(
-                "{}.{} ({} - {} - lap {})",
-                elapsed.as_secs(),
-                format!("{:09}", elapsed.subsec_nanos()),
-                self.ident,
-                msg,
-                lap_n,
-            );
-        } else {
-            eprintln!(
-                "\x1B[33m\x1B[1m{}.{} ({} - {} - lap {})\x1B[0m",
-                elapsed.as_secs(),
-                format!("{:09}", elapsed.subsec_nanos()),
-                self.ident,
-                msg,
-                lap_n,
-            );
-        }
+        let elapsed = self.clock.elapsed(base);
+        self.output.get_mut().write_measurement(Measurement {
+            kind: MeasurementKind::Lap,
+            ident: &self.ident,
+            label: msg,
+            elapsed,
+            precision: self.precision,
+            lap_n: Some(lap_n),
+            location: None,
+        });
 
-        self.lap = Some(std::time::Instant::now());
+        self.lap = Some(self.clock.now());
         self.lap_n = Some(lap_n + 1);
     }
 
     /// Prints the time from the starting point where the method is called. In contrast to `#lap()` this always shows
     /// the time elapsed from the start.
     pub fn split(&self, msg: &str) {
-        let elapsed = self.start.elapsed();
-        if cfg!(all(target_os = "windows", not(debug_assertions))) {
-            eprintln!(
-                "{}.{} ({} - {})",
-                elapsed.as_secs(),
-                format!("{:09}", elapsed.subsec_nanos()),
-                self.ident,
-                msg,
-            );
-        } else {
-            eprintln!(
-                "\x1B[33m\x1B[1m{}.{} ({} - {})\x1B[0m",
-                elapsed.as_secs(),
-                format!("{:09}", elapsed.subsec_nanos()),
-                self.ident,
-                msg
-            );
-        }
+        let elapsed = self.clock.elapsed(self.start);
+        self.output.borrow_mut().write_measurement(Measurement {
+            kind: MeasurementKind::Split,
+            ident: &self.ident,
+            label: msg,
+            elapsed,
+            precision: self.precision,
+            lap_n: None,
+            location: None,
+        });
     }
 
     /// Shows the end time and consumes self so the timer can not be used any further.
     pub fn end(self) {
-        let elapsed = self.start.elapsed();
-        if cfg!(all(target_os = "windows", not(debug_assertions))) {
-            eprintln!(
-                "{}.{} ({} - end) @ {}",
-                elapsed.as_secs(),
-                format!("{:09}", elapsed.subsec_nanos()),
-                self.ident,
-                self.start_line,
-            );
-        } else {
-            eprintln!(
-                "\x1B[33m\x1B[1m{}.{} ({} - end)\x1B[0m @ {}",
-                elapsed.as_secs(),
-                format!("{:09}", elapsed.subsec_nanos()),
-                self.ident,
-                self.start_line,
+        let elapsed = self.clock.elapsed(self.start);
+        self.output.into_inner().write_measurement(Measurement {
+            kind: MeasurementKind::End,
+            ident: &self.ident,
+            label: "",
+            elapsed,
+            precision: self.precision,
+            lap_n: None,
+            location: Some(&self.start_line),
+        });
+    }
+}
+
+/// A one-shot statistical benchmark: runs a closure `n` times and prints count/mean/min/max/
+/// std-dev computed over the samples (discarding the first as a warmup, when there's more
+/// than one). You get one of these from [`Perf::bench`].
+pub struct Bench<C: Clock = StdClock> {
+    ident: String,
+    clock: C,
+    output: Output,
+}
+
+impl Bench<StdClock> {
+    fn new(ident: String) -> Self {
+        Bench::new_with_clock(ident, StdClock)
+    }
+}
+
+impl<C: Clock> Bench<C> {
+    /// Like [`Perf::bench`], but measures time with a custom [`Clock`].
+    pub fn new_with_clock(ident: String, clock: C) -> Self {
+        Bench {
+            ident,
+            clock,
+            output: Output::stderr(),
+        }
+    }
+
+    /// Writes the summary to `writer` instead of stderr, e.g. to capture it into a log file.
+    pub fn to_writer<W: std::io::Write + 'static>(mut self, writer: W) -> Self {
+        self.output.writer = Box::new(writer);
+        self
+    }
+
+    /// Renders the summary as `format` instead of the default human-readable text.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.output.format = format;
+        self
+    }
+
+    /// Runs `f` `n` times, discards the first (warmup) sample when there's more than one, and
+    /// prints count/mean/min/max/std-dev computed over the rest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn run<T>(mut self, n: usize, mut f: impl FnMut() -> T) {
+        assert!(n > 0, "print_perf: Bench::run requires at least 1 iteration");
+
+        let mut durations = Vec::with_capacity(n);
+        for _ in 0..n {
+            let start = self.clock.now();
+            let _ = f();
+            durations.push(self.clock.elapsed(start));
+        }
+        if durations.len() > 1 {
+            durations.remove(0);
+        }
+
+        let count = durations.len();
+        let total = durations
+            .iter()
+            .fold(std::time::Duration::default(), |acc, d| acc + *d);
+        let mean = total / count as u32;
+        let min = *durations.iter().min().unwrap();
+        let max = *durations.iter().max().unwrap();
+        let variance = durations
+            .iter()
+            .map(|d| {
+                let diff = d.as_secs_f64() - mean.as_secs_f64();
+                diff * diff
+            })
+            .sum::<f64>()
+            / count as f64;
+        let std_dev = std::time::Duration::from_secs_f64(variance.sqrt());
+
+        let label = format!(
+            "count {}, mean {}, min {}, max {}, std dev {}",
+            count,
+            format_elapsed(mean, Precision::Auto),
+            format_elapsed(min, Precision::Auto),
+            format_elapsed(max, Precision::Auto),
+            format_elapsed(std_dev, Precision::Auto),
+        );
+        self.output.write_measurement(Measurement {
+            kind: MeasurementKind::Bench,
+            ident: &self.ident,
+            label: &label,
+            elapsed: mean,
+            precision: Precision::Auto,
+            lap_n: None,
+            location: None,
+        });
+    }
+}
+
+/// An aggregating timer: instead of printing each `lap` immediately, it buckets the
+/// elapsed durations by label and prints a count/total/min/max/mean/p50/p95 summary per
+/// label on [`AggPerf::end`]. You get one of these from [`Perf::aggregate`].
+pub struct AggPerf<C: Clock = StdClock> {
+    clock: C,
+    ident: String,
+    start: C::Instant,
+    lap: Option<C::Instant>,
+    samples: std::collections::HashMap<String, Vec<std::time::Duration>>,
+    output: Output,
+}
+
+impl AggPerf<StdClock> {
+    pub fn new(ident: String) -> Self {
+        AggPerf::new_with_clock(ident, StdClock)
+    }
+}
+
+impl<C: Clock> AggPerf<C> {
+    /// Like [`AggPerf::new`], but measures time with a custom [`Clock`].
+    pub fn new_with_clock(ident: String, clock: C) -> Self {
+        let start = clock.now();
+        AggPerf {
+            clock,
+            ident,
+            start,
+            lap: None,
+            samples: std::collections::HashMap::new(),
+            output: Output::stderr(),
+        }
+    }
+
+    /// Writes the summary to `writer` instead of stderr, e.g. to capture it into a log file.
+    pub fn to_writer<W: std::io::Write + 'static>(mut self, writer: W) -> Self {
+        self.output.writer = Box::new(writer);
+        self
+    }
+
+    /// Renders the summary as `format` instead of the default human-readable text.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.output.format = format;
+        self
+    }
+
+    /// Records the time elapsed since the last lap (or since this timer was created) into
+    /// `msg`'s bucket. Nothing is printed until [`AggPerf::end`] is called.
+    pub fn lap(&mut self, msg: &str) {
+        let base = self.lap.unwrap_or(self.start);
+        let elapsed = self.clock.elapsed(base);
+        self.samples
+            .entry(msg.to_string())
+            .or_default()
+            .push(elapsed);
+        self.lap = Some(self.clock.now());
+    }
+
+    /// Prints a count/total/min/max/mean/p50/p95 summary for every label recorded via
+    /// `lap`, then consumes self so the timer can not be used any further.
+    pub fn end(mut self) {
+        let mut labels: Vec<&String> = self.samples.keys().collect();
+        labels.sort();
+
+        for label in labels {
+            let mut durations = self.samples[label].clone();
+            durations.sort();
+
+            let count = durations.len();
+            let total = durations
+                .iter()
+                .fold(std::time::Duration::default(), |acc, d| acc + *d);
+            let min = durations[0];
+            let max = durations[count - 1];
+            let mean = total / count as u32;
+            let p50 = percentile(&durations, 0.50);
+            let p95 = percentile(&durations, 0.95);
+
+            let summary = format!(
+                "{}: count {}, total {}, min {}, max {}, mean {}, p50 {}, p95 {}",
+                label,
+                count,
+                format_elapsed(total, Precision::Auto),
+                format_elapsed(min, Precision::Auto),
+                format_elapsed(max, Precision::Auto),
+                format_elapsed(mean, Precision::Auto),
+                format_elapsed(p50, Precision::Auto),
+                format_elapsed(p95, Precision::Auto),
             );
+            self.output.write_measurement(Measurement {
+                kind: MeasurementKind::Agg,
+                ident: &self.ident,
+                label: &summary,
+                elapsed: mean,
+                precision: Precision::Auto,
+                lap_n: None,
+                location: None,
+            });
         }
     }
 }
 
+/// An RAII timer: prints its elapsed time automatically when it goes out of scope, so an
+/// early return or `?` can never silently drop the measurement the way forgetting to call
+/// [`Perf::end`] would. You get one of these from [`Perf::scoped`] or the [`perf_scoped!`] macro.
+pub struct PerfGuard<C: Clock = StdClock> {
+    clock: C,
+    start: C::Instant,
+    start_line: String,
+    ident: String,
+    precision: Precision,
+    output: Output,
+}
+
+impl PerfGuard<StdClock> {
+    fn new(ident: String, start_line: String) -> Self {
+        PerfGuard::new_with_clock(ident, start_line, StdClock)
+    }
+}
+
+impl<C: Clock> PerfGuard<C> {
+    /// Like [`PerfGuard::new`], but measures time with a custom [`Clock`].
+    pub fn new_with_clock(ident: String, start_line: String, clock: C) -> Self {
+        let start = clock.now();
+        PerfGuard {
+            clock,
+            start,
+            start_line,
+            ident,
+            precision: Precision::Auto,
+            output: Output::stderr(),
+        }
+    }
+
+    /// Overrides how the elapsed time is rendered when this guard is dropped.
+    pub fn with_precision(mut self, precision: Precision) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Redirects the measurement printed on drop to `writer` instead of stderr.
+    pub fn to_writer<W: std::io::Write + 'static>(mut self, writer: W) -> Self {
+        self.output.writer = Box::new(writer);
+        self
+    }
+
+    /// Renders the measurement printed on drop in a different [`Format`].
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.output.format = format;
+        self
+    }
+}
+
+impl<C: Clock> Drop for PerfGuard<C> {
+    fn drop(&mut self) {
+        let elapsed = self.clock.elapsed(self.start);
+        self.output.write_measurement(Measurement {
+            kind: MeasurementKind::Scoped,
+            ident: &self.ident,
+            label: "",
+            elapsed,
+            precision: self.precision,
+            lap_n: None,
+            location: Some(&self.start_line),
+        });
+    }
+}
+
+/// Percentile of an already-sorted slice of durations: the sample at index
+/// `p * (len - 1)`, rounded to the nearest index.
+fn percentile(sorted: &[std::time::Duration], p: f64) -> std::time::Duration {
+    if sorted.is_empty() {
+        return std::time::Duration::default();
+    }
+    let rank = (p * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
 /// Se crate documentation for example on how to use
 #[macro_export]
 macro_rules! perf {
@@ -181,6 +767,31 @@ macro_rules! perf {
     };
 }
 
+/// Times a closure and returns its value, printing the elapsed time with this call site's
+/// file/line, e.g. `let result = perf_span!("add fn", || add(4, 4));`.
+#[macro_export]
+macro_rules! perf_span {
+    ($label:expr, $body:expr) => {{
+        let start_line = format!("[{}:{}]", file!(), line!());
+        $crate::Perf::span($label, start_line, $body)
+    }};
+}
+
+/// Creates a [`PerfGuard`] that prints the elapsed time when it is dropped, capturing this
+/// call site's file/line, e.g. `let _g = perf_scoped!("request handler");`.
+#[macro_export]
+macro_rules! perf_scoped {
+    ($val:expr) => {
+        match $val {
+            i => {
+                let ident = format!("{}", i);
+                let start_line = format!("[{}:{}]", file!(), line!());
+                $crate::Perf::scoped(ident, start_line)
+            }
+        }
+    };
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,10 +801,16 @@ mod tests {
     }
     #[test]
     fn basic_example() {
-        // to see output use: cargo test -- --nocapture
-        let p = perf!("add fn");
-        let _result = add(4, 4);
+        let clock = MockClock::new();
+        let buf = SharedBuf::default();
+        let p = Perf::new_with_clock("add fn".to_string(), "[test]".to_string(), clock.clone())
+            .to_writer(buf.clone());
+        let _result = add_fast(4, 4);
+        clock.advance(std::time::Duration::from_millis(100));
         p.end();
+
+        let captured = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        assert!(captured.contains("100.0 ms (add fn - end) @ [test]"));
     }
 
     #[test]
@@ -208,12 +825,22 @@ mod tests {
 
     #[test]
     fn lap_test() {
-        let mut p = perf!("add fn");
-        let _result = add(4, 4);
+        let clock = MockClock::new();
+        let buf = SharedBuf::default();
+        let mut p = Perf::new_with_clock("add fn".to_string(), "[test]".to_string(), clock.clone())
+            .to_writer(buf.clone());
+        let _result = add_fast(4, 4);
+        clock.advance(std::time::Duration::from_millis(100));
         p.lap("add");
         let _div = _result / 2;
+        clock.advance(std::time::Duration::from_millis(50));
         p.lap("div");
         p.end();
+
+        let captured = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        assert!(captured.contains("100.0 ms (add fn - add - lap 1)"));
+        assert!(captured.contains("50.0 ms (add fn - div - lap 2)"));
+        assert!(captured.contains("150.0 ms (add fn - end) @ [test]"));
     }
 
     fn add_fast(a: i32, b: i32) -> i32 {
@@ -226,4 +853,186 @@ mod tests {
         let _result = add_fast(4, 4);
         p.end();
     }
+
+    #[test]
+    fn with_precision_raw_nanos() {
+        let p = perf!("add fn").with_precision(Precision::RawNanos);
+        let _result = add_fast(4, 4);
+        p.end();
+    }
+
+    #[test]
+    fn perf_span_returns_closure_value() {
+        let result = perf_span!("add fn", || add_fast(4, 4));
+        assert_eq!(result, 8);
+    }
+
+    #[test]
+    fn bench_runs_n_iterations() {
+        Perf::bench("add fn".to_string()).run(20, || add_fast(4, 4));
+    }
+
+    #[test]
+    fn bench_with_mock_clock_prints_exact_stats() {
+        let clock = MockClock::new();
+        let buf = SharedBuf::default();
+        // The first call to `f` advances the mock clock by 500ms and every later call by 10ms,
+        // so every sample after the discarded warmup is identical and the summary is fully
+        // deterministic.
+        let mut first = true;
+        Bench::new_with_clock("add fn".to_string(), clock.clone())
+            .to_writer(buf.clone())
+            .run(4, || {
+                if first {
+                    clock.advance(std::time::Duration::from_millis(500));
+                    first = false;
+                } else {
+                    clock.advance(std::time::Duration::from_millis(10));
+                }
+            });
+
+        let captured = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        assert!(captured.contains("count 3"));
+        assert!(captured.contains("mean 10.0 ms"));
+        assert!(captured.contains("min 10.0 ms"));
+        assert!(captured.contains("max 10.0 ms"));
+        assert!(captured.contains("std dev 0 ns"));
+    }
+
+    #[test]
+    fn scoped_guard_prints_on_drop() {
+        fn work() -> i32 {
+            let _guard = perf_scoped!("add fn");
+            add_fast(4, 4)
+        }
+        assert_eq!(work(), 8);
+    }
+
+    #[test]
+    fn scoped_guard_fires_on_early_return() {
+        fn work(early: bool) -> i32 {
+            let _guard = perf_scoped!("add fn");
+            if early {
+                return -1;
+            }
+            add_fast(4, 4)
+        }
+        assert_eq!(work(true), -1);
+    }
+
+    #[test]
+    fn scoped_guard_with_mock_clock() {
+        let clock = MockClock::new();
+        let buf = SharedBuf::default();
+        {
+            let _guard = PerfGuard::new_with_clock(
+                "add fn".to_string(),
+                "[test]".to_string(),
+                clock.clone(),
+            )
+            .to_writer(buf.clone());
+            clock.advance(std::time::Duration::from_millis(100));
+        }
+
+        let captured = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        assert!(captured.contains("100.0 ms"));
+        assert!(captured.contains("add fn"));
+        assert!(captured.contains("scoped end"));
+        assert!(captured.contains("[test]"));
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(std::rc::Rc<std::cell::RefCell<Vec<u8>>>);
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn to_writer_captures_json_records() {
+        let clock = MockClock::new();
+        let buf = SharedBuf::default();
+        let mut p = Perf::new_with_clock("add fn".to_string(), "[test]".to_string(), clock.clone())
+            .to_writer(buf.clone())
+            .with_format(Format::Json);
+        clock.advance(std::time::Duration::from_micros(412));
+        p.lap("add");
+        p.end();
+
+        let captured = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        assert!(captured.contains("\"kind\":\"lap\""));
+        assert!(captured.contains("\"label\":\"add\""));
+        assert!(captured.contains("\"kind\":\"end\""));
+    }
+
+    #[test]
+    fn to_writer_tsv_escapes_embedded_tabs_and_newlines() {
+        let clock = MockClock::new();
+        let buf = SharedBuf::default();
+        let mut p = Perf::new_with_clock("add fn".to_string(), "[test]".to_string(), clock.clone())
+            .to_writer(buf.clone())
+            .with_format(Format::Tsv);
+        clock.advance(std::time::Duration::from_micros(412));
+        p.lap("weird\tlabel\nwith-newline");
+        p.end();
+
+        let captured = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        assert!(captured.contains("weird\\tlabel\\nwith-newline"));
+        for line in captured.lines() {
+            assert_eq!(line.matches('\t').count(), 4);
+        }
+    }
+
+    #[test]
+    fn aggregate_collects_laps_by_label() {
+        let clock = MockClock::new();
+        let buf = SharedBuf::default();
+        let mut p = AggPerf::new_with_clock("loop".to_string(), clock.clone())
+            .to_writer(buf.clone());
+        for _ in 0..5 {
+            clock.advance(std::time::Duration::from_millis(10));
+            p.lap("step");
+        }
+        p.end();
+
+        let captured = String::from_utf8(buf.0.borrow().clone()).unwrap();
+        assert!(captured.contains(
+            "loop - step: count 5, total 50.0 ms, min 10.0 ms, max 10.0 ms, mean 10.0 ms, \
+             p50 10.0 ms, p95 10.0 ms"
+        ));
+    }
+
+    #[test]
+    fn percentile_picks_nearest_rank() {
+        use std::time::Duration;
+        let durations: Vec<Duration> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&durations, 0.50), Duration::from_millis(6));
+        assert_eq!(percentile(&durations, 0.95), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn format_auto_picks_unit() {
+        use std::time::Duration;
+        assert_eq!(format_auto(Duration::from_nanos(412)), "412 ns");
+        assert_eq!(format_auto(Duration::from_micros(125)), "125.0 µs");
+        assert_eq!(format_auto(Duration::from_millis(3)), "3.0 ms");
+        assert_eq!(format_auto(Duration::from_millis(1920)), "1.92 s");
+        assert_eq!(format_auto(Duration::from_secs(184)), "3 m 4.0 s");
+    }
+
+    #[test]
+    fn format_auto_bumps_unit_at_rounding_boundaries() {
+        use std::time::Duration;
+        // 999.96µs rounds to "1000.0" at 1 decimal, so it must render as ms, not µs.
+        assert_eq!(format_auto(Duration::from_nanos(999_960)), "1.0 ms");
+        // 999.999ms rounds to "1000.0" at 1 decimal, so it must render as s, not ms.
+        assert_eq!(format_auto(Duration::from_nanos(999_999_000)), "1.00 s");
+        // 59.999s rounds to "60.00" at 2 decimals, so it must render as minutes, not seconds.
+        assert_eq!(format_auto(Duration::from_secs_f64(59.999)), "1 m 0.0 s");
+    }
 }